@@ -0,0 +1,85 @@
+use std::{
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures_lite::stream::Stream;
+use tokio::io::DuplexStream;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+/// The buffer capacity of the in-memory pipe backing [`BytesStream::sink`].
+const SINK_CAPACITY: usize = 64 * 1024;
+
+/// An owned stream of byte chunks, following the `BytesStream` used by
+/// pict-rs to move request/response bodies in and out of ffmpeg without a
+/// temp-file round trip.
+pub struct BytesStream {
+  inner: Pin<Box<dyn Stream<Item = std::io::Result<Bytes>> + Send>>,
+}
+
+impl BytesStream {
+  /// Wraps any chunked byte stream, e.g. an uploaded request body.
+  pub fn new<S>(stream: S) -> Self
+  where
+      S: Stream<Item = std::io::Result<Bytes>> + Send + 'static,
+  {
+      Self {
+          inner: Box::pin(stream),
+      }
+  }
+
+  /// Adapts this stream into an [`AsyncRead`](tokio::io::AsyncRead), for use
+  /// as a streamed [`Input`](crate::Input).
+  pub fn into_reader(self) -> StreamReader<Self, Bytes> {
+      StreamReader::new(self)
+  }
+
+  /// Creates a connected pair: a sink ffmpeg can write a streamed
+  /// [`Output`](crate::Output) into, and the [`BytesStream`] that yields
+  /// whatever is written to it, chunk by chunk.
+  pub fn sink() -> (DuplexStream, Self) {
+      let (tx, rx) = tokio::io::duplex(SINK_CAPACITY);
+      (tx, Self::new(ReaderStream::new(rx)))
+  }
+}
+
+impl Stream for BytesStream {
+  type Item = std::io::Result<Bytes>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+      self.inner.as_mut().poll_next(cx)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+  use super::*;
+
+  #[tokio::test]
+  async fn into_reader_yields_the_wrapped_chunks() {
+      let chunks = vec![
+          std::io::Result::Ok(Bytes::from_static(b"hello ")),
+          Ok(Bytes::from_static(b"world")),
+      ];
+      let mut reader = BytesStream::new(futures_lite::stream::iter(chunks)).into_reader();
+
+      let mut buf = String::new();
+      reader.read_to_string(&mut buf).await.unwrap();
+      assert_eq!(buf, "hello world");
+  }
+
+  #[tokio::test]
+  async fn sink_collects_whatever_is_written_to_it() {
+      let (mut tx, stream) = BytesStream::sink();
+      tx.write_all(b"hello world").await.unwrap();
+      drop(tx); // close the write half so the reader sees EOF
+
+      let mut reader = stream.into_reader();
+      let mut buf = String::new();
+      reader.read_to_string(&mut buf).await.unwrap();
+      assert_eq!(buf, "hello world");
+  }
+}
@@ -0,0 +1,17 @@
+use std::process::ExitStatus;
+
+/// Errors produced while driving an ffmpeg process.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+  #[error("ffmpeg did not finish within the configured timeout")]
+  Timeout,
+
+  #[error("failed to spawn ffmpeg")]
+  Spawn(#[source] std::io::Error),
+
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+
+  #[error("ffmpeg exited with {status}: {stderr}")]
+  Failed { status: ExitStatus, stderr: String },
+}
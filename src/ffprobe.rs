@@ -0,0 +1,189 @@
+use std::process::Stdio;
+
+use serde::Deserialize;
+use tokio::{io::AsyncRead, process::Command};
+
+use crate::Input;
+
+/// Errors produced while running `ffprobe`.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+  #[error("failed to spawn ffprobe")]
+  Spawn(#[source] std::io::Error),
+
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+
+  #[error("ffprobe exited with {status}: {stderr}")]
+  Failed {
+      status: std::process::ExitStatus,
+      stderr: String,
+  },
+
+  #[error("failed to parse ffprobe output: {0}")]
+  Parse(#[from] serde_json::Error),
+}
+
+/// A single stream entry reported by `ffprobe`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Stream {
+  pub width: Option<u32>,
+  pub height: Option<u32>,
+  pub codec_name: Option<String>,
+  pub pix_fmt: Option<String>,
+  #[serde(default, deserialize_with = "from_str_opt")]
+  pub nb_read_frames: Option<u64>,
+}
+
+/// The `format` section reported by `ffprobe`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Format {
+  pub format_name: String,
+  /// Duration in seconds, if ffprobe could determine one (it prints `N/A`
+  /// for non-seekable inputs, e.g. when probing over `pipe:0`).
+  #[serde(deserialize_with = "duration_from_str")]
+  pub duration: Option<f64>,
+}
+
+/// Parsed output of `ffprobe -show_entries stream:format`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Probe {
+  pub streams: Vec<Stream>,
+  pub format: Format,
+}
+
+/// Parses a `duration`-like string, treating `N/A` (or any other unparsable
+/// value) as absent rather than an error.
+fn duration_from_str<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+  D: serde::Deserializer<'de>,
+{
+  let s = String::deserialize(deserializer)?;
+  Ok(s.parse().ok())
+}
+
+fn from_str_opt<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+  D: serde::Deserializer<'de>,
+{
+  let s = Option::<String>::deserialize(deserializer)?;
+  s.map(|s| s.parse().map_err(serde::de::Error::custom))
+      .transpose()
+}
+
+/// Builds an `ffprobe` invocation for a single [`Input`].
+pub struct FfprobeBuilder<'a, R>
+where
+  R: AsyncRead + Unpin,
+{
+  pub input: Input<'a, R>,
+}
+
+impl<'a> FfprobeBuilder<'a, tokio::io::Empty> {
+  pub fn file(path: impl Into<std::path::PathBuf>) -> Self {
+      Self::new(Input::file(path))
+  }
+}
+
+impl<'a, R> FfprobeBuilder<'a, R>
+where
+  R: AsyncRead + Unpin,
+{
+  pub fn new(input: Input<'a, R>) -> Self {
+      Self { input }
+  }
+
+  fn to_command(&self) -> Command {
+      let mut cmd = Command::new("ffprobe");
+
+      cmd.args([
+          "-v",
+          "quiet",
+          "-show_entries",
+          "stream=width,height,nb_read_frames,codec_name,pix_fmt:format=format_name,duration",
+          "-of",
+          "json",
+          "-print_format",
+          "json",
+      ]);
+
+      match &self.input {
+          Input::File(path) => {
+              cmd.arg(path);
+          }
+          Input::Stream(_) => {
+              cmd.arg("pipe:0");
+              cmd.stdin(Stdio::piped());
+          }
+      }
+
+      cmd.stdout(Stdio::piped());
+      cmd.stderr(Stdio::piped());
+
+      cmd
+  }
+
+  pub fn spawn(self) -> Result<Ffprobe<'a, R>, Error> {
+      let mut cmd = self.to_command();
+      let child = cmd.spawn().map_err(Error::Spawn)?;
+
+      let Self { input, .. } = self;
+
+      Ok(Ffprobe { child, input })
+  }
+}
+
+/// A running `ffprobe` process, spawned via [`FfprobeBuilder::spawn`].
+pub struct Ffprobe<'a, R>
+where
+  R: AsyncRead + Unpin,
+{
+  child: tokio::process::Child,
+  input: Input<'a, R>,
+}
+
+impl<R> Ffprobe<'_, R>
+where
+  R: AsyncRead + Unpin,
+{
+  /// Waits for `ffprobe` to finish and parses its JSON report.
+  pub async fn wait(&mut self) -> Result<Probe, Error> {
+      let mut stdout = self.child.stdout.take().unwrap();
+      let mut stderr = self.child.stderr.take().unwrap();
+      let mut out = Vec::new();
+      let mut err = Vec::new();
+
+      let copy = tokio::io::copy(&mut stdout, &mut out);
+      let copy_err = tokio::io::copy(&mut stderr, &mut err);
+      let stdin = async {
+          match self.input {
+              Input::File(_) => std::io::Result::Ok(()),
+              Input::Stream(ref mut read) => {
+                  let mut stdin = self.child.stdin.take().unwrap();
+                  tokio::io::copy(read, &mut stdin).await?;
+                  drop(stdin); // drop to close stdin
+                  Ok(())
+              }
+          }
+      };
+
+      let ((copy, copy_err), stdin) = futures_lite::future::zip(
+          futures_lite::future::zip(copy, copy_err),
+          stdin,
+      )
+      .await;
+      copy?;
+      copy_err?;
+      stdin?;
+
+      let status = self.child.wait().await?;
+      if !status.success() {
+          return Err(Error::Failed {
+              status,
+              stderr: String::from_utf8_lossy(&err).into_owned(),
+          });
+      }
+
+      Ok(serde_json::from_slice(&out)?)
+  }
+}
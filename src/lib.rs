@@ -1,34 +1,43 @@
-use std::{path::PathBuf, process::Stdio, ffi::{OsStr, OsString}};
+use std::{path::PathBuf, pin::Pin, process::Stdio, ffi::{OsStr, OsString}, time::Duration, future::Future};
 
 use futures_lite::future::zip;
-use tokio::{net::{UnixListener, UnixStream}, io::{AsyncRead, AsyncWrite, BufReader, AsyncBufReadExt, Sink, Empty}, process::Command, sync::mpsc};
-
-struct TempSocket {
-  dir: tempfile::TempDir,
-  path: PathBuf,
-  listener: UnixListener,
-}
-
-impl std::ops::Deref for TempSocket {
-  type Target = UnixListener;
-
-  fn deref(&self) -> &Self::Target {
-      &self.listener
-  }
-}
-
-impl TempSocket {
-  pub fn new() -> std::io::Result<Self> {
-      let dir = tempfile::tempdir()?;
-      let path = dir.path().join("sock");
-      let listener = UnixListener::bind(&path)?;
-      Ok(Self {
-          dir,
-          path,
-          listener,
-      })
-  }
-}
+#[cfg(unix)]
+use nix::{sys::signal::{self, Signal}, unistd::Pid};
+use tokio::{io::{AsyncRead, AsyncWrite, Sink, Empty}, process::Command, sync::mpsc};
+
+mod bytes;
+mod error;
+mod ffprobe;
+#[cfg(unix)]
+mod pipe;
+mod progress;
+mod transport;
+
+pub use bytes::BytesStream;
+pub use error::Error;
+pub use ffprobe::{Error as ProbeError, Ffprobe, FfprobeBuilder, Format, Probe, Stream as ProbeStream};
+pub use progress::{Progress, ProgressStatus};
+
+#[cfg(unix)]
+use pipe::{ExtraInput, ExtraOutput};
+use transport::ProgressListener;
+
+/// Stand-in for [`pipe::ExtraInput`] on platforms without extra streamed
+/// inputs beyond stdin; never constructed.
+#[cfg(not(unix))]
+enum ExtraInput {}
+
+/// Stand-in for [`pipe::ExtraOutput`] on platforms without extra streamed
+/// outputs beyond stdout; never constructed.
+#[cfg(not(unix))]
+enum ExtraOutput {}
+
+/// How long to wait after SIGTERM before escalating to SIGKILL.
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// The first file descriptor available for streamed inputs/outputs beyond
+/// stdin (0), stdout (1) and stderr (2).
+const FIRST_EXTRA_FD: i32 = 3;
 
 pub enum Input<'a, R> {
   File(PathBuf),
@@ -36,7 +45,7 @@ pub enum Input<'a, R> {
 }
 
 impl Input<'_, Empty> {
-  fn file(path: impl Into<PathBuf>) -> Self {
+  pub(crate) fn file(path: impl Into<PathBuf>) -> Self {
       Self::File(path.into())
   }
 }
@@ -52,16 +61,56 @@ impl Output<'_, Sink> {
   }
 }
 
+/// Where a streamed input's bytes are fed into the child process.
+enum InputDest {
+  Stdin(tokio::process::ChildStdin),
+  #[cfg(unix)]
+  Pipe(tokio::net::unix::pipe::Sender),
+}
+
+/// Where a streamed output's bytes are read from the child process.
+enum OutputSrc {
+  Stdout(tokio::process::ChildStdout),
+  #[cfg(unix)]
+  Pipe(tokio::net::unix::pipe::Receiver),
+}
+
+/// How an input/output slot was (or wasn't) wired while building the
+/// command, filled in with real handles once the child has spawned.
+enum Wire<T> {
+  None,
+  Stdio,
+  Pipe(T),
+}
+
+type BoxedCopy<'a> = Pin<Box<dyn Future<Output = std::io::Result<()>> + 'a>>;
+
+/// Drives every copy future to completion, then returns the first error in
+/// list order, if any.
+async fn join_all(copies: Vec<BoxedCopy<'_>>) -> std::io::Result<()> {
+  let mut acc: BoxedCopy = Box::pin(async { Ok(()) });
+  for fut in copies {
+      acc = Box::pin(async move {
+          let (a, b) = zip(acc, fut).await;
+          a?;
+          b?;
+          Ok(())
+      });
+  }
+  acc.await
+}
+
 pub struct FfmpegBuilder<'a, R, W>
 where
   R: AsyncRead + Unpin,
   W: AsyncWrite + Unpin,
 {
   pub global_options: Vec<OsString>,
-  pub input_options: Vec<OsString>,
-  pub input: Input<'a, R>,
-  pub output_options: Vec<OsString>,
-  pub output: Output<'a, W>,
+  pub inputs: Vec<(Vec<OsString>, Input<'a, R>)>,
+  pub outputs: Vec<(Vec<OsString>, Output<'a, W>)>,
+  /// Maximum time to let ffmpeg run before it is killed and `wait()` returns
+  /// [`Error::Timeout`]. `None` means wait forever.
+  pub timeout: Option<Duration>,
 }
 
 impl<'a, R, W> FfmpegBuilder<'a, R, W>
@@ -69,101 +118,169 @@ where
   R: AsyncRead + Unpin,
   W: AsyncWrite + Unpin,
 {
-  fn to_command(&self, progress_url: impl AsRef<OsStr>) -> Command {
+  #[allow(clippy::type_complexity)]
+  fn to_command(
+      &self,
+      progress_url: impl AsRef<OsStr>,
+  ) -> std::io::Result<(Command, Vec<Wire<ExtraInput>>, Vec<Wire<ExtraOutput>>)> {
       // ffmpeg [global_options] {[input_file_options] -i input_url} ... {[output_file_options] output_url} ...
 
       let mut cmd = Command::new("ffmpeg");
+      let mut next_fd = FIRST_EXTRA_FD;
+      let mut stdin_taken = false;
+      let mut stdout_taken = false;
 
       cmd.arg("-progress");
       cmd.arg(progress_url);
       cmd.args(&self.global_options);
 
-      cmd.args(&self.input_options);
-      cmd.arg("-i");
-      match &self.input {
-          Input::File(path) => {
-              cmd.arg(path);
-          }
-          Input::Stream(_) => {
-              cmd.arg("pipe:0"); // stdin
-              cmd.stdin(Stdio::piped());
-          }
+      let mut input_wires = Vec::with_capacity(self.inputs.len());
+      for (options, input) in &self.inputs {
+          cmd.args(options);
+          cmd.arg("-i");
+
+          let wire = match input {
+              Input::File(path) => {
+                  cmd.arg(path);
+                  Wire::None
+              }
+              Input::Stream(_) if !stdin_taken => {
+                  stdin_taken = true;
+                  cmd.arg("pipe:0");
+                  cmd.stdin(Stdio::piped());
+                  Wire::Stdio
+              }
+              #[cfg(unix)]
+              Input::Stream(_) => {
+                  let fd = next_fd;
+                  next_fd += 1;
+                  cmd.arg(format!("pipe:{fd}"));
+                  Wire::Pipe(ExtraInput::reserve(&mut cmd, fd)?)
+              }
+              #[cfg(not(unix))]
+              Input::Stream(_) => {
+                  return Err(std::io::Error::new(
+                      std::io::ErrorKind::Unsupported,
+                      "only one streamed input is supported on this platform",
+                  ));
+              }
+          };
+          input_wires.push(wire);
       }
 
-      cmd.args(&self.output_options);
-      match &self.output {
-          Output::File(path) => {
-              cmd.arg(path);
-          }
-          Output::Stream(_) => {
-              cmd.arg("pipe:1"); // stdout
-              cmd.stdout(Stdio::piped());
-          }
+      let mut output_wires = Vec::with_capacity(self.outputs.len());
+      for (options, output) in &self.outputs {
+          cmd.args(options);
+
+          let wire = match output {
+              Output::File(path) => {
+                  cmd.arg(path);
+                  Wire::None
+              }
+              Output::Stream(_) if !stdout_taken => {
+                  stdout_taken = true;
+                  cmd.arg("pipe:1");
+                  cmd.stdout(Stdio::piped());
+                  Wire::Stdio
+              }
+              #[cfg(unix)]
+              Output::Stream(_) => {
+                  let fd = next_fd;
+                  next_fd += 1;
+                  cmd.arg(format!("pipe:{fd}"));
+                  Wire::Pipe(ExtraOutput::reserve(&mut cmd, fd)?)
+              }
+              #[cfg(not(unix))]
+              Output::Stream(_) => {
+                  return Err(std::io::Error::new(
+                      std::io::ErrorKind::Unsupported,
+                      "only one streamed output is supported on this platform",
+                  ));
+              }
+          };
+          output_wires.push(wire);
       }
 
-      cmd
+      cmd.stderr(Stdio::piped());
+
+      Ok((cmd, input_wires, output_wires))
   }
 
-  pub fn spawn(self) -> anyhow::Result<Ffmpeg<'a, R, W>> {
-      let progress_sock = TempSocket::new()?;
-      let mut cmd = self.to_command(format!("unix://{}", progress_sock.path.to_str().unwrap()));
-      let child = cmd.spawn()?;
+  pub async fn spawn(self) -> Result<Ffmpeg<'a, R, W>, Error> {
+      let progress_listener = ProgressListener::bind().await?;
+      let (mut cmd, input_wires, output_wires) = self.to_command(progress_listener.url())?;
+      let mut child = cmd.spawn().map_err(Error::Spawn)?;
+
+      let mut stdin = child.stdin.take();
+      let mut stdout = child.stdout.take();
+      let stderr = child.stderr.take().unwrap();
 
       let (mut progress_tx, progress_rx) = mpsc::unbounded_channel();
       tokio::spawn(async move {
-          let (mut stream, _) = progress_sock.accept().await.unwrap();
-          read_progress(&mut stream, &mut progress_tx).await;
+          let stream = progress_listener.accept().await.unwrap();
+          progress::read_progress(stream, &mut progress_tx).await;
       });
 
-      let Self { input, output, .. } = self;
+      let Self { inputs, outputs, timeout, .. } = self;
+
+      let inputs = inputs
+          .into_iter()
+          .zip(input_wires)
+          .map(|((_, input), wire)| {
+              let dest = match wire {
+                  Wire::None => None,
+                  Wire::Stdio => Some(InputDest::Stdin(
+                      stdin.take().expect("stdin piped for this input"),
+                  )),
+                  #[cfg(unix)]
+                  Wire::Pipe(extra) => Some(InputDest::Pipe(extra.into_sender())),
+                  #[cfg(not(unix))]
+                  Wire::Pipe(extra) => match extra {},
+              };
+              (input, dest)
+          })
+          .collect();
+
+      let outputs = outputs
+          .into_iter()
+          .zip(output_wires)
+          .map(|((_, output), wire)| {
+              let src = match wire {
+                  Wire::None => None,
+                  Wire::Stdio => Some(OutputSrc::Stdout(
+                      stdout.take().expect("stdout piped for this output"),
+                  )),
+                  #[cfg(unix)]
+                  Wire::Pipe(extra) => Some(OutputSrc::Pipe(extra.into_receiver())),
+                  #[cfg(not(unix))]
+                  Wire::Pipe(extra) => match extra {},
+              };
+              (output, src)
+          })
+          .collect();
 
       Ok(Ffmpeg {
           child,
-          input,
-          output,
+          inputs,
+          outputs,
+          stderr,
           progress_rx,
+          timeout,
       })
   }
 }
 
-async fn read_progress(stream: &mut UnixStream, tx: &mut mpsc::UnboundedSender<Progress>) {
-  let mut lines = BufReader::new(stream).lines();
-
-  let mut progress = Progress::default();
-
-  while let Some(line) = lines.next_line().await.unwrap() {
-      if let Some((k, v)) = line.split_once('=') {
-          match k {
-              "total_size" => {
-                  progress.total_size = Some(v.parse().unwrap());
-              },
-              "progress" => {
-                  dbg!(progress);
-                  progress = Progress::default();
-              }
-              _ => {
-                  println!("unknown progress key: {}", k);
-              },
-          }
-          println!("{}: {}", k, v);
-      }
-  }
-}
-
 pub struct Ffmpeg<'a, R, W>
 where
   R: AsyncRead + Unpin,
   W: AsyncWrite + Unpin,
 {
   child: tokio::process::Child,
-  input: Input<'a, R>,
-  output: Output<'a, W>,
+  inputs: Vec<(Input<'a, R>, Option<InputDest>)>,
+  outputs: Vec<(Output<'a, W>, Option<OutputSrc>)>,
+  stderr: tokio::process::ChildStderr,
   progress_rx: mpsc::UnboundedReceiver<Progress>,
-}
-
-#[derive(Debug, Default)]
-struct Progress {
-  total_size: Option<u64>,
+  timeout: Option<Duration>,
 }
 
 impl<R, W> Ffmpeg<'_, R, W>
@@ -171,44 +288,106 @@ where
   R: AsyncRead + Unpin,
   W: AsyncWrite + Unpin,
 {
-  pub async fn wait(&mut self) -> anyhow::Result<()> {
-      let mut stdout = self.child.stdout.take().unwrap();
-      let mut stdin = self.child.stdin.take().unwrap();
-
-      let stdout = async {
-          match self.output {
-              Output::File(_) => std::io::Result::Ok(()),
-              Output::Stream(ref mut write) => {
-                  tokio::io::copy(&mut stdout, write).await?;
-                  drop(stdout); // drop to close stdout
+  /// The channel of progress reports emitted by ffmpeg as it runs.
+  pub fn progress(&mut self) -> &mut mpsc::UnboundedReceiver<Progress> {
+      &mut self.progress_rx
+  }
+
+  pub async fn wait(&mut self) -> Result<(), Error> {
+      let run = async {
+          let mut stderr_buf = Vec::new();
+          let stderr: BoxedCopy = Box::pin(async {
+              tokio::io::copy(&mut self.stderr, &mut stderr_buf).await?;
+              std::io::Result::Ok(())
+          });
+
+          let mut copies: Vec<BoxedCopy> = vec![stderr];
+
+          for (input, dest) in &mut self.inputs {
+              let dest = dest.take();
+              copies.push(Box::pin(async move {
+                  match (input, dest) {
+                      (Input::Stream(read), Some(InputDest::Stdin(mut w))) => {
+                          tokio::io::copy(read, &mut w).await?;
+                      }
+                      #[cfg(unix)]
+                      (Input::Stream(read), Some(InputDest::Pipe(mut w))) => {
+                          tokio::io::copy(read, &mut w).await?;
+                      }
+                      _ => {}
+                  }
                   Ok(())
-              }
+              }));
           }
-      };
-      let stdin = async {
-          match self.input {
-              Input::File(_) => std::io::Result::Ok(()),
-              Input::Stream(ref mut read) => {
-                  tokio::io::copy(read, &mut stdin).await?;
-                  drop(stdin); // drop to close stdin
+
+          for (output, src) in &mut self.outputs {
+              let src = src.take();
+              copies.push(Box::pin(async move {
+                  match (output, src) {
+                      (Output::Stream(write), Some(OutputSrc::Stdout(mut r))) => {
+                          tokio::io::copy(&mut r, write).await?;
+                      }
+                      #[cfg(unix)]
+                      (Output::Stream(write), Some(OutputSrc::Pipe(mut r))) => {
+                          tokio::io::copy(&mut r, write).await?;
+                      }
+                      _ => {}
+                  }
                   Ok(())
-              }
+              }));
           }
-      };
 
-      tokio::select! {
-          _ = self.child.wait() => {},
-          (read, write) = zip(stdout, stdin) => {
-              read?;
-              write?;
+          // Zipped, not raced: if `child.wait()` resolves first we still need
+          // the drain to run to completion (in particular `stderr_buf` must
+          // see everything ffmpeg wrote before we read it below), since the
+          // pipes only reach EOF/error once the exited child's fds are gone.
+          let (status, copy_result) = zip(self.child.wait(), join_all(copies)).await;
+          let status = status?;
+          copy_result?;
+
+          if !status.success() {
+              return Err(Error::Failed {
+                  status,
+                  stderr: String::from_utf8_lossy(&stderr_buf).into_owned(),
+              });
           }
+
+          Ok(())
+      };
+
+      match self.timeout {
+          Some(duration) => match tokio::time::timeout(duration, run).await {
+              Ok(result) => result,
+              Err(_) => {
+                  self.kill().await;
+                  Err(Error::Timeout)
+              }
+          },
+          None => run.await,
       }
+  }
 
-      if !self.child.wait().await?.success() {
-          panic!("ffmpeg failed");
+  /// Sends SIGTERM, then escalates to SIGKILL if ffmpeg hasn't exited after
+  /// [`KILL_GRACE_PERIOD`].
+  #[cfg(unix)]
+  async fn kill(&mut self) {
+      if let Some(pid) = self.child.id() {
+          let _ = signal::kill(Pid::from_raw(pid as i32), Signal::SIGTERM);
+
+          if tokio::time::timeout(KILL_GRACE_PERIOD, self.child.wait())
+              .await
+              .is_err()
+          {
+              let _ = self.child.kill().await;
+          }
       }
+  }
 
-      Ok(())
+  /// There's no SIGTERM on this platform, so this goes straight for the
+  /// forceful kill (`TerminateProcess` on Windows).
+  #[cfg(not(unix))]
+  async fn kill(&mut self) {
+      let _ = self.child.start_kill();
   }
 }
 
@@ -220,12 +399,12 @@ mod tests {
   async fn test() {
       let mut ffmpeg = FfmpegBuilder {
           global_options: vec![],
-          input_options: vec![],
-          input: Input::file(PathBuf::from("video.webm")),
-          output_options: vec![],
-          output: Output::file(PathBuf::from("test2.mp3")),
+          inputs: vec![(vec![], Input::file(PathBuf::from("video.webm")))],
+          outputs: vec![(vec![], Output::file(PathBuf::from("test2.mp3")))],
+          timeout: None,
       }
       .spawn()
+      .await
       .unwrap();
 
       ffmpeg.wait().await.unwrap();
@@ -0,0 +1,91 @@
+use std::{
+  fs::File,
+  os::fd::{AsRawFd, OwnedFd},
+};
+
+use nix::{
+  fcntl::{fcntl, FcntlArg, OFlag},
+  unistd::dup2,
+};
+use tokio::{net::unix::pipe, process::Command};
+
+fn set_nonblocking(fd: &OwnedFd) -> std::io::Result<()> {
+  let flags = OFlag::from_bits_truncate(fcntl(fd.as_raw_fd(), FcntlArg::F_GETFL)?);
+  fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(flags | OFlag::O_NONBLOCK))?;
+  Ok(())
+}
+
+/// The parent-side handle for an extra ffmpeg input beyond stdin, wired to
+/// `child_fd` inside the child via `pre_exec`.
+///
+/// Its read end is kept open (via `keep_alive`) until after the child has
+/// been spawned and inherited it, then dropped so the pipe closes once
+/// ffmpeg is done reading.
+pub(crate) struct ExtraInput {
+  pub sender: pipe::Sender,
+  keep_alive: OwnedFd,
+}
+
+impl ExtraInput {
+  /// Wires a fresh pipe into `cmd`'s child at `child_fd`, to be referenced
+  /// as `pipe:{child_fd}` on the ffmpeg command line.
+  pub(crate) fn reserve(cmd: &mut Command, child_fd: i32) -> std::io::Result<Self> {
+      let (read, write) = nix::unistd::pipe()?;
+      set_nonblocking(&write)?;
+
+      let read_raw = read.as_raw_fd();
+      // SAFETY: only async-signal-safe calls between fork and exec.
+      unsafe {
+          cmd.pre_exec(move || {
+              dup2(read_raw, child_fd)?;
+              Ok(())
+          });
+      }
+
+      Ok(Self {
+          sender: pipe::Sender::from_file(File::from(write))?,
+          keep_alive: read,
+      })
+  }
+
+  /// Releases the parent's copy of the read end. Call only after the child
+  /// has been spawned.
+  pub(crate) fn into_sender(self) -> pipe::Sender {
+      drop(self.keep_alive);
+      self.sender
+  }
+}
+
+/// The parent-side handle for an extra ffmpeg output beyond stdout, wired to
+/// `child_fd` inside the child via `pre_exec`. See [`ExtraInput`] for the
+/// keep-alive rationale.
+pub(crate) struct ExtraOutput {
+  pub receiver: pipe::Receiver,
+  keep_alive: OwnedFd,
+}
+
+impl ExtraOutput {
+  pub(crate) fn reserve(cmd: &mut Command, child_fd: i32) -> std::io::Result<Self> {
+      let (read, write) = nix::unistd::pipe()?;
+      set_nonblocking(&read)?;
+
+      let write_raw = write.as_raw_fd();
+      // SAFETY: only async-signal-safe calls between fork and exec.
+      unsafe {
+          cmd.pre_exec(move || {
+              dup2(write_raw, child_fd)?;
+              Ok(())
+          });
+      }
+
+      Ok(Self {
+          receiver: pipe::Receiver::from_file(File::from(read))?,
+          keep_alive: write,
+      })
+  }
+
+  pub(crate) fn into_receiver(self) -> pipe::Receiver {
+      drop(self.keep_alive);
+      self.receiver
+  }
+}
@@ -0,0 +1,126 @@
+use tokio::{
+  io::{AsyncBufReadExt, AsyncRead, BufReader},
+  sync::mpsc,
+};
+
+/// One `-progress` report emitted by ffmpeg.
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+  pub frame: u64,
+  pub fps: f64,
+  /// Bitrate in kbit/s, if ffmpeg reported one (it prints `N/A` early on).
+  pub bitrate: Option<f64>,
+  pub total_size: u64,
+  pub out_time_us: u64,
+  pub dup_frames: u64,
+  pub drop_frames: u64,
+  /// Encoding speed as a multiple of realtime, if ffmpeg reported one.
+  pub speed: Option<f64>,
+  pub status: ProgressStatus,
+}
+
+/// Whether ffmpeg has more progress reports coming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProgressStatus {
+  #[default]
+  Continue,
+  End,
+  /// The progress pipe broke (or carried non-UTF8 data) before ffmpeg ever
+  /// reported `progress=end`. Distinct from `End` so callers can tell a
+  /// broken pipe apart from ffmpeg finishing normally.
+  Errored,
+}
+
+fn parse_suffixed(v: &str, suffix: &str) -> Option<f64> {
+  v.strip_suffix(suffix)?.parse().ok()
+}
+
+pub(crate) async fn read_progress(
+  stream: impl AsyncRead + Unpin,
+  tx: &mut mpsc::UnboundedSender<Progress>,
+) {
+  let mut lines = BufReader::new(stream).lines();
+
+  let mut progress = Progress::default();
+
+  loop {
+      let line = match lines.next_line().await {
+          Ok(Some(line)) => line,
+          Ok(None) => break,
+          Err(_) => {
+              // the pipe died or ffmpeg wrote non-UTF8; surface it as a
+              // terminal report rather than silently going quiet.
+              progress.status = ProgressStatus::Errored;
+              let _ = tx.send(std::mem::take(&mut progress));
+              break;
+          }
+      };
+
+      let Some((k, v)) = line.split_once('=') else {
+          continue;
+      };
+
+      match k {
+          "frame" => progress.frame = v.parse().unwrap_or_default(),
+          "fps" => progress.fps = v.parse().unwrap_or_default(),
+          "bitrate" => progress.bitrate = parse_suffixed(v.trim(), "kbits/s"),
+          "total_size" => progress.total_size = v.parse().unwrap_or_default(),
+          "out_time_us" => progress.out_time_us = v.parse().unwrap_or_default(),
+          "dup_frames" => progress.dup_frames = v.parse().unwrap_or_default(),
+          "drop_frames" => progress.drop_frames = v.parse().unwrap_or_default(),
+          "speed" => progress.speed = parse_suffixed(v.trim(), "x"),
+          "progress" => {
+              progress.status = match v {
+                  "end" => ProgressStatus::End,
+                  _ => ProgressStatus::Continue,
+              };
+              let _ = tx.send(std::mem::take(&mut progress));
+          }
+          _ => {}
+      }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn parses_a_progress_report() {
+      let input = "frame=100\n\
+                   fps=25.0\n\
+                   bitrate=N/A\n\
+                   total_size=1024\n\
+                   out_time_us=4000000\n\
+                   dup_frames=1\n\
+                   drop_frames=2\n\
+                   speed=1.5x\n\
+                   progress=continue\n";
+
+      let (mut tx, mut rx) = mpsc::unbounded_channel();
+      read_progress(input.as_bytes(), &mut tx).await;
+
+      let progress = rx.recv().await.unwrap();
+      assert_eq!(progress.frame, 100);
+      assert_eq!(progress.fps, 25.0);
+      assert_eq!(progress.bitrate, None);
+      assert_eq!(progress.total_size, 1024);
+      assert_eq!(progress.out_time_us, 4_000_000);
+      assert_eq!(progress.dup_frames, 1);
+      assert_eq!(progress.drop_frames, 2);
+      assert_eq!(progress.speed, Some(1.5));
+      assert_eq!(progress.status, ProgressStatus::Continue);
+  }
+
+  #[tokio::test]
+  async fn surfaces_non_utf8_progress_as_errored() {
+      let input: &[u8] = b"frame=5\n\xff\xfe";
+
+      let (mut tx, mut rx) = mpsc::unbounded_channel();
+      read_progress(input, &mut tx).await;
+
+      let progress = rx.recv().await.unwrap();
+      assert_eq!(progress.frame, 5);
+      assert_eq!(progress.status, ProgressStatus::Errored);
+  }
+}
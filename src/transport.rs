@@ -0,0 +1,71 @@
+//! The channel ffmpeg's `-progress` reports are sent over.
+//!
+//! On Unix we listen on a Unix domain socket in a temp dir, same as before.
+//! Elsewhere (e.g. Windows) there's no such thing, so we fall back to a
+//! TCP listener on localhost. `read_progress` only needs `AsyncRead`, so
+//! this is the only place that needs to know the difference.
+
+#[cfg(unix)]
+pub(crate) use unix::ProgressListener;
+#[cfg(not(unix))]
+pub(crate) use tcp::ProgressListener;
+
+#[cfg(unix)]
+mod unix {
+  use std::path::PathBuf;
+
+  use tokio::net::{UnixListener, UnixStream};
+
+  pub(crate) struct ProgressListener {
+      _dir: tempfile::TempDir,
+      path: PathBuf,
+      listener: UnixListener,
+  }
+
+  impl ProgressListener {
+      pub(crate) async fn bind() -> std::io::Result<Self> {
+          let dir = tempfile::tempdir()?;
+          let path = dir.path().join("sock");
+          let listener = UnixListener::bind(&path)?;
+          Ok(Self {
+              _dir: dir,
+              path,
+              listener,
+          })
+      }
+
+      pub(crate) fn url(&self) -> String {
+          format!("unix://{}", self.path.to_str().unwrap())
+      }
+
+      pub(crate) async fn accept(&self) -> std::io::Result<UnixStream> {
+          Ok(self.listener.accept().await?.0)
+      }
+  }
+}
+
+#[cfg(not(unix))]
+mod tcp {
+  use tokio::net::{TcpListener, TcpStream};
+
+  pub(crate) struct ProgressListener {
+      listener: TcpListener,
+      port: u16,
+  }
+
+  impl ProgressListener {
+      pub(crate) async fn bind() -> std::io::Result<Self> {
+          let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+          let port = listener.local_addr()?.port();
+          Ok(Self { listener, port })
+      }
+
+      pub(crate) fn url(&self) -> String {
+          format!("tcp://127.0.0.1:{}", self.port)
+      }
+
+      pub(crate) async fn accept(&self) -> std::io::Result<TcpStream> {
+          Ok(self.listener.accept().await?.0)
+      }
+  }
+}